@@ -17,20 +17,79 @@
 //! Polkadot-specific GRANDPA integration utilities.
 
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::str::FromStr;
 
-use sp_runtime::traits::{Block as BlockT, NumberFor};
+use sp_runtime::traits::{Block as BlockT, NumberFor, UniqueSaturatedInto, UniqueSaturatedFrom};
 use sp_runtime::generic::BlockId;
 use sp_runtime::traits::Header as _;
+use prometheus_endpoint::{self, Registry};
 
 #[cfg(feature = "real-overseer")]
 use {
 	polkadot_primitives::v1::{Block as PolkadotBlock, Header as PolkadotHeader, BlockNumber},
 	polkadot_subsystem::messages::ApprovalVotingMessage,
-	prometheus_endpoint::{self, Registry},
 	polkadot_overseer::OverseerHandler,
 	futures::channel::oneshot,
 };
 
+/// Log target shared by every GRANDPA voting rule in this module, so operators
+/// can filter on a single target regardless of which rule is active.
+const LOG_TARGET: &str = "grandpa_voting_rule";
+
+/// Prometheus metrics shared by every voting rule in this module. Registered
+/// once against the node's [`Registry`] and cloned into each rule that needs
+/// to report on it, so a dashboard can show restriction state across whichever
+/// rules are actually composed into the running chain.
+#[derive(Clone)]
+pub(crate) struct Metrics {
+	restricted_target: prometheus_endpoint::Gauge<prometheus_endpoint::U64>,
+	pause_active: prometheus_endpoint::Gauge<prometheus_endpoint::U64>,
+	restriction_distance: prometheus_endpoint::Gauge<prometheus_endpoint::U64>,
+}
+
+impl Metrics {
+	/// Register the shared voting rule metrics against `registry`. Must only
+	/// be called once per registry; share the result via `clone()`.
+	pub(crate) fn register(registry: &Registry) -> Result<Self, prometheus_endpoint::PrometheusError> {
+		Ok(Metrics {
+			restricted_target: prometheus_endpoint::register(
+				prometheus_endpoint::Gauge::new(
+					"grandpa_voting_rule_restricted_target",
+					"The block number the active GRANDPA voting rule is restricting votes to",
+				)?,
+				registry,
+			)?,
+			pause_active: prometheus_endpoint::register(
+				prometheus_endpoint::Gauge::new(
+					"grandpa_pause_active",
+					"Whether the GRANDPA pause voting rule is currently holding finality at a fixed block (1) or not (0)",
+				)?,
+				registry,
+			)?,
+			restriction_distance: prometheus_endpoint::register(
+				prometheus_endpoint::Gauge::new(
+					"grandpa_restriction_distance",
+					"Best block number minus the block number the active GRANDPA voting rule returned",
+				)?,
+				registry,
+			)?,
+		})
+	}
+
+	/// Record that a rule restricted the vote to `target_number`, `best_number`
+	/// behind the head.
+	fn observe_restriction(&self, best_number: u64, target_number: u64) {
+		self.restricted_target.set(target_number);
+		self.restriction_distance.set(best_number.saturating_sub(target_number));
+	}
+
+	/// Record whether the pause rule is currently engaged.
+	fn set_pause_active(&self, active: bool) {
+		self.pause_active.set(active as u64);
+	}
+}
+
 /// A custom GRANDPA voting rule that acts as a diagnostic for the approval
 /// voting subsystem's desired votes.
 ///
@@ -156,7 +215,7 @@ impl<B> grandpa::VotingRule<PolkadotBlock, B> for ApprovalCheckingDiagnostic
 			}
 
 			tracing::debug!(
-				target: "approval_voting",
+				target: LOG_TARGET,
 				"GRANDPA: voting on {:?}. Approval-checking lag behind best is {}",
 				actual_vote_target,
 				approval_checking_subsystem_lag,
@@ -167,12 +226,241 @@ impl<B> grandpa::VotingRule<PolkadotBlock, B> for ApprovalCheckingDiagnostic
 	}
 }
 
+/// The maximum number of blocks behind the head that [`ApprovalCheckingVotingRule`]
+/// will allow finality to lag when the approval voting subsystem can't be
+/// reached in time, so that a stuck subsystem can never stall finality outright.
+#[cfg(feature = "real-overseer")]
+const DEFAULT_MAX_APPROVAL_LAG: BlockNumber = 50;
+
+/// Clamp `resolved_number` into the range the voter actually allows us to vote
+/// in (`[base_number, current_target_number]`), then fold it through
+/// `high_water_mark` so the result doesn't suggest a lower target than any
+/// call has previously returned -- unless `current_target_number` has itself
+/// retreated below the mark, in which case the best we can still do is return
+/// `current_target_number`, the highest header the chain can currently reach.
+///
+/// Pulled out of [`ApprovalCheckingVotingRule::restrict_vote`] as a plain
+/// function, independent of the `real-overseer` feature and `OverseerHandler`,
+/// so the clamp arithmetic itself can be unit tested directly.
+fn clamp_to_high_water_mark(
+	high_water_mark: &AtomicU64,
+	resolved_number: u64,
+	current_target_number: u64,
+	base_number: u64,
+) -> u64 {
+	let target_number = std::cmp::max(
+		std::cmp::min(resolved_number, current_target_number),
+		base_number,
+	);
+
+	let previous_mark = high_water_mark.fetch_max(target_number, Ordering::SeqCst);
+	let mark = std::cmp::max(target_number, previous_mark);
+
+	std::cmp::min(mark, current_target_number)
+}
+
+/// A custom GRANDPA voting rule that enforces the vote suggested by the approval
+/// voting subsystem, rather than merely observing it like [`ApprovalCheckingDiagnostic`]
+/// does.
+///
+/// The subsystem is asked for the highest ancestor of the best block that has
+/// been fully approved. If it has nothing approved above `base` the rule votes
+/// on `base`. If it can't answer in time (or the query errors) the rule falls
+/// back to a configurable maximum lag behind the head, so finality always makes
+/// progress even while approval checking is unavailable.
+#[cfg(feature = "real-overseer")]
+#[derive(Clone)]
+pub(crate) struct ApprovalCheckingVotingRule {
+	checking_lag: Option<prometheus_endpoint::Histogram>,
+	overseer: OverseerHandler,
+	max_lag: BlockNumber,
+	// the highest target number this rule has ever returned, so that a later
+	// call (e.g. across a reorg) won't suggest voting lower than before, unless
+	// `current_target` has itself retreated below that mark, in which case the
+	// best we can do is vote on `current_target` until the chain recovers.
+	high_water_mark: Arc<AtomicU64>,
+	metrics: Option<Metrics>,
+}
+
+#[cfg(feature = "real-overseer")]
+impl ApprovalCheckingVotingRule {
+	/// Create a new approval checking voting rule which enforces the subsystem's
+	/// vote, falling back to `DEFAULT_MAX_APPROVAL_LAG` blocks behind the head
+	/// if the subsystem can't be reached in time.
+	pub fn new(overseer: OverseerHandler, registry: Option<&Registry>)
+		-> Result<Self, prometheus_endpoint::PrometheusError>
+	{
+		Self::with_max_lag(overseer, DEFAULT_MAX_APPROVAL_LAG, registry)
+	}
+
+	/// Same as `new`, but with a configurable maximum lag fallback.
+	pub fn with_max_lag(overseer: OverseerHandler, max_lag: BlockNumber, registry: Option<&Registry>)
+		-> Result<Self, prometheus_endpoint::PrometheusError>
+	{
+		Ok(ApprovalCheckingVotingRule {
+			// named distinctly from `ApprovalCheckingDiagnostic`'s histogram so that
+			// the two rules can be registered against the same `Registry` without
+			// `prometheus_endpoint::register` failing with `AlreadyReg`, in case a
+			// deployment ever wires up both the diagnostic and the enforcing rule.
+			checking_lag: if let Some(registry) = registry {
+				Some(prometheus_endpoint::register(
+					prometheus_endpoint::Histogram::with_opts(
+						prometheus_endpoint::HistogramOpts::new(
+							"approval_enforcing_finality_lag",
+							"How far behind the head of the chain the Approval Checking protocol wants to vote, as enforced by ApprovalCheckingVotingRule",
+						).buckets(vec![1.0, 2.0, 3.0, 4.0, 5.0, 10.0, 20.0, 30.0, 40.0, 50.0])
+					)?,
+					registry,
+				)?)
+			} else {
+				None
+			},
+			overseer,
+			max_lag,
+			high_water_mark: Arc::new(AtomicU64::new(0)),
+			metrics: None,
+		})
+	}
+
+	/// Attach the shared voting rule [`Metrics`] (registered once for the whole
+	/// composed rule chain) to this rule.
+	pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+		self.metrics = Some(metrics);
+		self
+	}
+}
+
+#[cfg(feature = "real-overseer")]
+impl<B> grandpa::VotingRule<PolkadotBlock, B> for ApprovalCheckingVotingRule
+	where B: sp_blockchain::HeaderBackend<PolkadotBlock>
+{
+	fn restrict_vote(
+		&self,
+		backend: Arc<B>,
+		base: &PolkadotHeader,
+		best_target: &PolkadotHeader,
+		current_target: &PolkadotHeader,
+	) -> grandpa::VotingRuleResult<PolkadotBlock> {
+		let find_target = |target_number: BlockNumber, current_header: &PolkadotHeader| {
+			let mut target_hash = current_header.hash();
+			let mut target_header = current_header.clone();
+
+			loop {
+				if *target_header.number() < target_number {
+					unreachable!(
+						"we are traversing backwards from a known block; \
+						blocks are stored contiguously; \
+						qed"
+					);
+				}
+
+				if *target_header.number() == target_number {
+					return Some((target_hash, target_number));
+				}
+
+				target_hash = *target_header.parent_hash();
+				target_header = backend.header(BlockId::Hash(target_hash)).ok()?
+					.expect("Header known to exist due to the existence of one of its descendents; qed");
+			}
+		};
+
+		let mut overseer = self.overseer.clone();
+		let checking_lag = self.checking_lag.clone();
+		let metrics = self.metrics.clone();
+		let max_lag = self.max_lag;
+		let high_water_mark = self.high_water_mark.clone();
+
+		let best_hash = best_target.hash();
+		let best_number = best_target.number.clone();
+		let base_hash = base.hash();
+		let base_number = base.number;
+		let current_target_number = current_target.number.clone();
+		let current_target = current_target.clone();
+
+		Box::pin(async move {
+			let (tx, rx) = oneshot::channel();
+			overseer.send_msg(ApprovalVotingMessage::ApprovedAncestor(
+				best_hash,
+				base_number,
+				tx,
+			)).await;
+
+			// bound how long we wait on the subsystem so that it can never
+			// stall finality outright if it's stuck or slow to respond.
+			let timeout = futures_timer::Delay::new(std::time::Duration::from_secs(2));
+			futures::pin_mut!(rx);
+			futures::pin_mut!(timeout);
+
+			let resolved_number = match futures::future::select(rx, timeout).await {
+				futures::future::Either::Left((Ok(Some((_, n))), _)) => n,
+				futures::future::Either::Left((Ok(None), _)) => base_number,
+				futures::future::Either::Left((Err(_), _)) | futures::future::Either::Right(_) =>
+					best_number.saturating_sub(max_lag),
+			};
+
+			let target_number = clamp_to_high_water_mark(
+				&high_water_mark,
+				resolved_number as u64,
+				current_target_number as u64,
+				base_number as u64,
+			) as BlockNumber;
+
+			let actual_vote_target = if target_number == base_number {
+				Some((base_hash, base_number))
+			} else if target_number >= current_target_number {
+				Some((current_target.hash(), current_target_number))
+			} else {
+				find_target(target_number, &current_target)
+			};
+
+			let lag = best_number.saturating_sub(target_number);
+
+			if let Some(ref checking_lag) = checking_lag {
+				checking_lag.observe(lag as _);
+			}
+			if let Some(ref metrics) = metrics {
+				metrics.observe_restriction(best_number as u64, target_number as u64);
+			}
+
+			tracing::debug!(
+				target: LOG_TARGET,
+				"GRANDPA: enforcing approval-checking vote on {:?}. Lag behind best is {}",
+				actual_vote_target,
+				lag,
+			);
+
+			actual_vote_target
+		})
+	}
+}
+
 /// A custom GRANDPA voting rule that "pauses" voting (i.e. keeps voting for the
 /// same last finalized block) after a given block at height `N` has been
 /// finalized and for a delay of `M` blocks, i.e. until the best block reaches
 /// `N` + `M`, the voter will keep voting for block `N`.
+///
+/// The rule's suggested target is non-decreasing across calls, except when
+/// `current_target` itself retreats below a previously suggested target (as
+/// can happen transiently across a reorg) and stays there -- in that case the
+/// rule falls back to the best target `current_target` can actually reach,
+/// since it can't suggest voting on a header the chain doesn't have.
 #[derive(Clone)]
-pub(crate) struct PauseAfterBlockFor<N>(pub(crate) N, pub(crate) N);
+pub(crate) struct PauseAfterBlockFor<N>(pub(crate) N, pub(crate) N, Arc<AtomicU64>, Option<Metrics>);
+
+impl<N> PauseAfterBlockFor<N> {
+	/// Create a new pause rule, pausing after block `after` is finalized for
+	/// up to `for_blocks` blocks.
+	pub(crate) fn new(after: N, for_blocks: N) -> Self {
+		PauseAfterBlockFor(after, for_blocks, Arc::new(AtomicU64::new(0)), None)
+	}
+
+	/// Attach the shared voting rule [`Metrics`] (registered once for the whole
+	/// composed rule chain) to this rule.
+	pub(crate) fn with_metrics(mut self, metrics: Metrics) -> Self {
+		self.3 = Some(metrics);
+		self
+	}
+}
 
 impl<Block, B> grandpa::VotingRule<Block, B> for PauseAfterBlockFor<NumberFor<Block>>
 where
@@ -186,32 +474,32 @@ where
 		best_target: &Block::Header,
 		current_target: &Block::Header,
 	) -> grandpa::VotingRuleResult<Block> {
-		let aux = || {
-			// walk backwards until we find the target block
-			let find_target = |target_number: NumberFor<Block>, current_header: &Block::Header| {
-				let mut target_hash = current_header.hash();
-				let mut target_header = current_header.clone();
-
-				loop {
-					if *target_header.number() < target_number {
-						unreachable!(
-							"we are traversing backwards from a known block; \
-							 blocks are stored contiguously; \
-							 qed"
-						);
-					}
-
-					if *target_header.number() == target_number {
-						return Some((target_hash, target_number));
-					}
+		// walk backwards until we find the target block
+		let find_target = |target_number: NumberFor<Block>, current_header: &Block::Header| {
+			let mut target_hash = current_header.hash();
+			let mut target_header = current_header.clone();
 
-					target_hash = *target_header.parent_hash();
-					target_header = backend.header(BlockId::Hash(target_hash)).ok()?.expect(
-						"Header known to exist due to the existence of one of its descendents; qed",
+			loop {
+				if *target_header.number() < target_number {
+					unreachable!(
+						"we are traversing backwards from a known block; \
+						 blocks are stored contiguously; \
+						 qed"
 					);
 				}
-			};
 
+				if *target_header.number() == target_number {
+					return Some((target_hash, target_number));
+				}
+
+				target_hash = *target_header.parent_hash();
+				target_header = backend.header(BlockId::Hash(target_hash)).ok()?.expect(
+					"Header known to exist due to the existence of one of its descendents; qed",
+				);
+			}
+		};
+
+		let aux = || {
 			// only restrict votes targeting a block higher than the block
 			// we've set for the pause
 			if *current_target.number() > self.0 {
@@ -235,12 +523,203 @@ where
 			None
 		};
 
-		let target = aux();
+		let candidate = aux();
+
+		// don't suggest a lower target than we've previously returned, even if
+		// a reorg made `current_target` retreat in the meantime -- unless
+		// `current_target` itself can no longer reach the mark, in which case we
+		// clamp down to it below (see the doc comment on this struct).
+		let base_number = *base.number();
+		let current_target_number = *current_target.number();
+		let candidate_number = candidate.map(|(_, n)| n).unwrap_or(current_target_number);
+
+		let mark: u64 = candidate_number.unique_saturated_into();
+		let previous_mark = self.2.fetch_max(mark, Ordering::SeqCst);
+		let floor = std::cmp::max(mark, previous_mark);
+		self.2.fetch_max(floor, Ordering::SeqCst);
+
+		let target = if floor == mark {
+			// no regression - this round's result stands.
+			candidate
+		} else {
+			let floor_number = NumberFor::<Block>::unique_saturated_from(floor);
+
+			if floor_number >= current_target_number {
+				// the mark is ahead of what this chain can currently reach;
+				// this is the best we can do without inventing a future block.
+				Some((current_target.hash(), current_target_number))
+			} else {
+				// `floor` only exceeds `mark` (this branch) when it came from
+				// `previous_mark`, i.e. some earlier round's `candidate_number`,
+				// which is always `>= base_number` (it's `base_number` itself,
+				// `self.0` which exceeds it, or `current_target_number` which
+				// does by construction) -- so `floor_number <= base_number` here
+				// is impossible.
+				debug_assert!(floor_number > base_number);
+				find_target(floor_number, current_target)
+			}
+		};
+
+		if let Some(ref metrics) = self.3 {
+			// `candidate.is_some()` reflects whether the configured pause window
+			// (`self.0`/`self.1`) is actually engaged. `target` may stay `Some`
+			// a little longer than that due to the high-water-mark fallback
+			// above, which is not the same thing as the pause being active.
+			metrics.set_pause_active(candidate.is_some());
+
+			// observe unconditionally, even when `target` is `None` (the pause
+			// has released through ordinary forward progress) -- otherwise the
+			// gauges stay pinned at their last value instead of tracking the
+			// chain advancing past the restriction.
+			let restricted_number = target.map(|(_, n)| n).unwrap_or(current_target_number);
+			let best_number: u64 = (*best_target.number()).unique_saturated_into();
+			metrics.observe_restriction(best_number, restricted_number.unique_saturated_into());
+		}
+
+		tracing::debug!(
+			target: LOG_TARGET,
+			"GRANDPA pause rule: vote target {:?}",
+			target,
+		);
 
 		Box::pin(async move { target })
 	}
 }
 
+/// One parsed segment of a `--grandpa-voting-rule` spec, before it's composed
+/// into a concrete [`grandpa::VotingRule`] chain by [`VotingRuleConfig::build`].
+///
+/// Mirrors the way database pruning modes are parsed: a single CLI string is
+/// first turned into this intermediate representation, which is validated
+/// independently of anything feature-gated, and only later converted into the
+/// concrete rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VotingRuleSpec {
+	/// Pause voting after block `N` for up to `M` blocks.
+	Pause(u32, u32),
+	/// Enforce the approval-checking subsystem's suggested vote.
+	ApprovalEnforce,
+	/// Override the maximum lag fallback used by `ApprovalEnforce`.
+	MaxLag(u32),
+}
+
+impl FromStr for VotingRuleSpec {
+	type Err = String;
+
+	fn from_str(segment: &str) -> Result<Self, Self::Err> {
+		let segment = segment.trim();
+
+		if segment == "approval-enforce" {
+			return Ok(VotingRuleSpec::ApprovalEnforce);
+		}
+
+		if let Some(rest) = segment.strip_prefix("pause:") {
+			let (after, for_blocks) = rest.split_once('+').ok_or_else(|| format!(
+				"invalid pause spec {:?}, expected \"pause:N+M\"", segment,
+			))?;
+
+			let after: u32 = after.parse()
+				.map_err(|_| format!("invalid pause block number in {:?}", segment))?;
+			let for_blocks: u32 = for_blocks.parse()
+				.map_err(|_| format!("invalid pause duration in {:?}", segment))?;
+
+			if for_blocks == 0 {
+				return Err(format!(
+					"pause duration must be greater than 0, a pause of 0 blocks would never release: {:?}",
+					segment,
+				));
+			}
+
+			return Ok(VotingRuleSpec::Pause(after, for_blocks));
+		}
+
+		if let Some(rest) = segment.strip_prefix("max-lag:") {
+			let max_lag: u32 = rest.parse()
+				.map_err(|_| format!("invalid max-lag value in {:?}", segment))?;
+
+			return Ok(VotingRuleSpec::MaxLag(max_lag));
+		}
+
+		Err(format!("unknown voting rule spec {:?}", segment))
+	}
+}
+
+/// A runtime-configurable GRANDPA voting rule chain, parsed from a single CLI
+/// string such as `"pause:20+30,approval-enforce,max-lag:50"`.
+///
+/// This lets operators choose, per deployment, whether to run a pause rule,
+/// the approval-enforcing rule, or both, without a rebuild. No spec selects
+/// `ApprovalCheckingDiagnostic`; it is never composed by `VotingRuleConfig::build`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct VotingRuleConfig {
+	rules: Vec<VotingRuleSpec>,
+}
+
+impl FromStr for VotingRuleConfig {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let s = s.trim();
+		if s.is_empty() {
+			return Ok(VotingRuleConfig::default());
+		}
+
+		let rules = s.split(',')
+			.map(VotingRuleSpec::from_str)
+			.collect::<Result<Vec<_>, _>>()?;
+
+		Ok(VotingRuleConfig { rules })
+	}
+}
+
+#[cfg(feature = "real-overseer")]
+impl VotingRuleConfig {
+	/// Compose this configuration into a concrete GRANDPA voting rule, wiring
+	/// in the approval-voting overseer handler and metrics registry needed by
+	/// the `approval-enforce` and `max-lag` specs.
+	pub(crate) fn build<B>(
+		&self,
+		overseer: OverseerHandler,
+		registry: Option<&Registry>,
+	) -> Result<impl grandpa::VotingRule<PolkadotBlock, B>, prometheus_endpoint::PrometheusError>
+	where
+		B: sp_blockchain::HeaderBackend<PolkadotBlock> + 'static,
+	{
+		let max_lag = self.rules.iter().find_map(|rule| match rule {
+			VotingRuleSpec::MaxLag(n) => Some(*n),
+			_ => None,
+		}).unwrap_or(DEFAULT_MAX_APPROVAL_LAG);
+
+		// register the shared metrics once for the whole chain, then clone
+		// them into whichever rules are actually composed below.
+		let metrics = registry.map(Metrics::register).transpose()?;
+
+		let mut builder = grandpa::VotingRulesBuilder::default();
+
+		for rule in &self.rules {
+			builder = match rule {
+				VotingRuleSpec::Pause(after, for_blocks) => {
+					let mut rule = PauseAfterBlockFor::new(*after, *for_blocks);
+					if let Some(ref metrics) = metrics {
+						rule = rule.with_metrics(metrics.clone());
+					}
+					builder.add(rule)
+				}
+				VotingRuleSpec::ApprovalEnforce => {
+					let mut rule = ApprovalCheckingVotingRule::with_max_lag(overseer.clone(), max_lag, registry)?;
+					if let Some(ref metrics) = metrics {
+						rule = rule.with_metrics(metrics.clone());
+					}
+					builder.add(rule)
+				}
+				VotingRuleSpec::MaxLag(_) => builder,
+			};
+		}
+
+		Ok(builder.build())
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use grandpa::VotingRule;
@@ -252,6 +731,47 @@ mod tests {
 	use sp_runtime::{generic::BlockId, traits::Header};
 	use consensus_common::BlockOrigin;
 	use std::sync::Arc;
+	use std::sync::atomic::AtomicU64;
+
+	#[test]
+	fn clamp_to_high_water_mark_stays_within_base_and_current_target() {
+		let mark = AtomicU64::new(0);
+
+		// `resolved_number` above `current_target_number` is clamped down to it.
+		assert_eq!(super::clamp_to_high_water_mark(&mark, 100, 40, 10), 40);
+
+		// `resolved_number` below `base_number` is clamped up to it.
+		let mark = AtomicU64::new(0);
+		assert_eq!(super::clamp_to_high_water_mark(&mark, 0, 40, 10), 10);
+	}
+
+	#[test]
+	fn clamp_to_high_water_mark_never_regresses_while_current_target_holds() {
+		let mark = AtomicU64::new(0);
+
+		assert_eq!(super::clamp_to_high_water_mark(&mark, 30, 40, 0), 30);
+
+		// a later call resolves lower, but `current_target` can still reach the
+		// mark, so the previous result stands.
+		assert_eq!(super::clamp_to_high_water_mark(&mark, 10, 40, 0), 30);
+		assert_eq!(super::clamp_to_high_water_mark(&mark, 10, 35, 0), 30);
+	}
+
+	#[test]
+	fn clamp_to_high_water_mark_falls_back_when_current_target_retreats_below_it() {
+		let mark = AtomicU64::new(0);
+
+		assert_eq!(super::clamp_to_high_water_mark(&mark, 30, 40, 0), 30);
+
+		// `current_target` itself retreats below the established mark (e.g. a
+		// reorg); there's no header above it to vote on, so the best we can do
+		// is clamp down to `current_target_number`.
+		assert_eq!(super::clamp_to_high_water_mark(&mark, 10, 25, 0), 25);
+
+		// once `current_target` recovers past the mark, the rule resumes
+		// enforcing it.
+		assert_eq!(super::clamp_to_high_water_mark(&mark, 10, 35, 0), 30);
+	}
 
 	#[test]
 	fn grandpa_pause_voting_rule_works() {
@@ -277,7 +797,7 @@ mod tests {
 
 		// the rule should filter all votes after block #20
 		// is finalized until block #50 is imported.
-		let voting_rule = super::PauseAfterBlockFor(20, 30);
+		let voting_rule = super::PauseAfterBlockFor::new(20, 30);
 
 		// add 10 blocks
 		push_blocks(10);
@@ -363,4 +883,322 @@ mod tests {
 			None,
 		);
 	}
+
+	#[test]
+	fn grandpa_pause_voting_rule_never_regresses_its_target() {
+		let _ = env_logger::try_init();
+
+		let client = Arc::new(TestClientBuilder::new().build());
+
+		let mut push_blocks = {
+			let mut client = client.clone();
+
+			move |n| {
+				for _ in 0..n {
+					let block = client.init_polkadot_block_builder().build().unwrap().block;
+					client.import(BlockOrigin::Own, block).unwrap();
+				}
+			}
+		};
+
+		let get_header = {
+			let client = client.clone();
+			move |n| client.header(&BlockId::Number(n)).unwrap().unwrap()
+		};
+
+		push_blocks(40);
+
+		// pauses indefinitely (for all practical purposes) once past block #5,
+		// so every call below is restricted.
+		let voting_rule = super::PauseAfterBlockFor::new(5, 1_000);
+
+		// we've finalized block #30, so the rule votes on it.
+		let target_30 = get_header(30);
+		assert_eq!(
+			futures::executor::block_on(voting_rule.restrict_vote(
+				client.clone(),
+				&target_30,
+				&get_header(40),
+				&get_header(40),
+			)),
+			Some((target_30.hash(), 30)),
+		);
+
+		// `base` (and `current_target`) retreat, as can happen transiently
+		// across a reorg; the rule must not suggest voting below block #30
+		// again, even though a naive recomputation would now pick #10.
+		assert_eq!(
+			futures::executor::block_on(voting_rule.restrict_vote(
+				client.clone(),
+				&get_header(10),
+				&get_header(35),
+				&get_header(35),
+			)),
+			Some((target_30.hash(), 30)),
+		);
+
+		// `current_target` retreats further still (but remains high enough to
+		// reach block #30); the output must still not drop.
+		assert_eq!(
+			futures::executor::block_on(voting_rule.restrict_vote(
+				client.clone(),
+				&get_header(10),
+				&get_header(32),
+				&get_header(32),
+			)),
+			Some((target_30.hash(), 30)),
+		);
+	}
+
+	#[test]
+	fn grandpa_pause_voting_rule_falls_back_when_target_retreats_below_its_mark() {
+		let _ = env_logger::try_init();
+
+		let client = Arc::new(TestClientBuilder::new().build());
+
+		let mut push_blocks = {
+			let mut client = client.clone();
+
+			move |n| {
+				for _ in 0..n {
+					let block = client.init_polkadot_block_builder().build().unwrap().block;
+					client.import(BlockOrigin::Own, block).unwrap();
+				}
+			}
+		};
+
+		let get_header = {
+			let client = client.clone();
+			move |n| client.header(&BlockId::Number(n)).unwrap().unwrap()
+		};
+
+		push_blocks(40);
+
+		// pauses indefinitely (for all practical purposes) once past block #5.
+		let voting_rule = super::PauseAfterBlockFor::new(5, 1_000);
+
+		// we've finalized block #30, so the rule votes on it, establishing a
+		// high-water mark of 30.
+		let target_30 = get_header(30);
+		assert_eq!(
+			futures::executor::block_on(voting_rule.restrict_vote(
+				client.clone(),
+				&target_30,
+				&get_header(40),
+				&get_header(40),
+			)),
+			Some((target_30.hash(), 30)),
+		);
+
+		// `current_target` retreats *below* the established mark (e.g. a deep
+		// reorg). The rule can't invent a header above `current_target`, so it
+		// falls back to the best it can still reach instead of regressing
+		// silently or stalling.
+		let target_25 = get_header(25);
+		assert_eq!(
+			futures::executor::block_on(voting_rule.restrict_vote(
+				client.clone(),
+				&get_header(10),
+				&target_25,
+				&target_25,
+			)),
+			Some((target_25.hash(), 25)),
+		);
+
+		// once `current_target` recovers past the mark, the rule resumes
+		// enforcing it rather than getting stuck at the fallback.
+		assert_eq!(
+			futures::executor::block_on(voting_rule.restrict_vote(
+				client.clone(),
+				&get_header(10),
+				&get_header(35),
+				&get_header(35),
+			)),
+			Some((target_30.hash(), 30)),
+		);
+	}
+
+	#[test]
+	fn voting_rule_config_parses_a_composite_spec() {
+		let config: super::VotingRuleConfig = "pause:20+30,approval-enforce,max-lag:50"
+			.parse()
+			.unwrap();
+
+		assert_eq!(
+			config.rules,
+			vec![
+				super::VotingRuleSpec::Pause(20, 30),
+				super::VotingRuleSpec::ApprovalEnforce,
+				super::VotingRuleSpec::MaxLag(50),
+			],
+		);
+	}
+
+	#[test]
+	fn voting_rule_config_parses_empty_spec_as_no_rules() {
+		let config: super::VotingRuleConfig = "".parse().unwrap();
+		assert_eq!(config, super::VotingRuleConfig::default());
+	}
+
+	#[test]
+	fn voting_rule_config_rejects_zero_length_pause() {
+		assert!("pause:20+0".parse::<super::VotingRuleConfig>().is_err());
+	}
+
+	#[test]
+	fn voting_rule_config_rejects_negative_and_overflowing_numbers() {
+		assert!("pause:-1+30".parse::<super::VotingRuleConfig>().is_err());
+		assert!("pause:20+99999999999999999999".parse::<super::VotingRuleConfig>().is_err());
+		assert!("max-lag:-5".parse::<super::VotingRuleConfig>().is_err());
+	}
+
+	#[test]
+	fn voting_rule_config_rejects_unknown_spec() {
+		assert!("not-a-real-rule".parse::<super::VotingRuleConfig>().is_err());
+	}
+
+	#[test]
+	fn shared_metrics_observe_pause_rule_restriction() {
+		let _ = env_logger::try_init();
+
+		let client = Arc::new(TestClientBuilder::new().build());
+
+		let mut push_blocks = {
+			let mut client = client.clone();
+
+			move |n| {
+				for _ in 0..n {
+					let block = client.init_polkadot_block_builder().build().unwrap().block;
+					client.import(BlockOrigin::Own, block).unwrap();
+				}
+			}
+		};
+
+		let get_header = {
+			let client = client.clone();
+			move |n| client.header(&BlockId::Number(n)).unwrap().unwrap()
+		};
+
+		push_blocks(25);
+
+		let registry = prometheus_endpoint::Registry::new();
+		let metrics = super::Metrics::register(&registry).unwrap();
+		let voting_rule = super::PauseAfterBlockFor::new(20, 30).with_metrics(metrics.clone());
+
+		let _ = futures::executor::block_on(voting_rule.restrict_vote(
+			client.clone(),
+			&get_header(10),
+			&get_header(21),
+			&get_header(21),
+		));
+
+		assert_eq!(metrics.pause_active.get(), 1);
+		assert_eq!(metrics.restricted_target.get(), 20);
+		assert_eq!(metrics.restriction_distance.get(), 1);
+	}
+
+	#[test]
+	fn grandpa_pause_active_metric_reflects_the_real_pause_window_not_the_fallback() {
+		let _ = env_logger::try_init();
+
+		let client = Arc::new(TestClientBuilder::new().build());
+
+		let mut push_blocks = {
+			let mut client = client.clone();
+
+			move |n| {
+				for _ in 0..n {
+					let block = client.init_polkadot_block_builder().build().unwrap().block;
+					client.import(BlockOrigin::Own, block).unwrap();
+				}
+			}
+		};
+
+		let get_header = {
+			let client = client.clone();
+			move |n| client.header(&BlockId::Number(n)).unwrap().unwrap()
+		};
+
+		push_blocks(25);
+
+		let registry = prometheus_endpoint::Registry::new();
+		let metrics = super::Metrics::register(&registry).unwrap();
+		// pause after block #5, for up to 10 blocks (i.e. until best > 15).
+		let voting_rule = super::PauseAfterBlockFor::new(5, 10).with_metrics(metrics.clone());
+
+		// we're inside the pause window: pause_active should be true.
+		let _ = futures::executor::block_on(voting_rule.restrict_vote(
+			client.clone(),
+			&get_header(10),
+			&get_header(12),
+			&get_header(12),
+		));
+		assert_eq!(metrics.pause_active.get(), 1);
+
+		// the pause window has since lapsed (best is well past #15) and
+		// `current_target` has also retreated below the high-water mark
+		// established above. The high-water-mark fallback still suggests a
+		// concrete target here, but the real pause condition no longer holds,
+		// so `pause_active` must report false.
+		let _ = futures::executor::block_on(voting_rule.restrict_vote(
+			client.clone(),
+			&get_header(5),
+			&get_header(20),
+			&get_header(8),
+		));
+		assert_eq!(metrics.pause_active.get(), 0);
+	}
+
+	#[test]
+	fn grandpa_restriction_metrics_keep_updating_after_the_pause_releases() {
+		let _ = env_logger::try_init();
+
+		let client = Arc::new(TestClientBuilder::new().build());
+
+		let mut push_blocks = {
+			let mut client = client.clone();
+
+			move |n| {
+				for _ in 0..n {
+					let block = client.init_polkadot_block_builder().build().unwrap().block;
+					client.import(BlockOrigin::Own, block).unwrap();
+				}
+			}
+		};
+
+		let get_header = {
+			let client = client.clone();
+			move |n| client.header(&BlockId::Number(n)).unwrap().unwrap()
+		};
+
+		push_blocks(60);
+
+		let registry = prometheus_endpoint::Registry::new();
+		let metrics = super::Metrics::register(&registry).unwrap();
+		// pause after block #20, for up to 30 blocks (i.e. until best > 50).
+		let voting_rule = super::PauseAfterBlockFor::new(20, 30).with_metrics(metrics.clone());
+
+		// we're past the pause block and inside the window: restricted to #20.
+		let _ = futures::executor::block_on(voting_rule.restrict_vote(
+			client.clone(),
+			&get_header(10),
+			&get_header(25),
+			&get_header(25),
+		));
+		assert_eq!(metrics.pause_active.get(), 1);
+		assert_eq!(metrics.restricted_target.get(), 20);
+
+		// ordinary forward progress (no reorg) carries best past #50: the pause
+		// releases and `target` becomes `None`. The gauges must keep tracking
+		// the chain rather than staying pinned at the stale restricted value.
+		let _ = futures::executor::block_on(voting_rule.restrict_vote(
+			client.clone(),
+			&get_header(20),
+			&get_header(55),
+			&get_header(55),
+		));
+		assert_eq!(metrics.pause_active.get(), 0);
+		assert_eq!(metrics.restricted_target.get(), 55);
+		assert_eq!(metrics.restriction_distance.get(), 0);
+	}
 }